@@ -1,8 +1,52 @@
+use cosmwasm_std::Coin;
 use cosmwasm_vm::{BackendApi, BackendError, BackendResult, GasInfo};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use crate::error::GoError;
 use crate::memory::{U8SliceView, UnmanagedVector};
 
+// Reject codes for dynamic-link calls, analogous to the rejection codes
+// ic-cdk's inter-canister call API returns alongside the raw reply. They let
+// callers distinguish transient, retryable failures from permanent ones
+// without having to parse the error message.
+//
+// `0` (`Success`) means the callee returned normally; any nonzero code is a
+// reject, even if the Go side also reports `GoError::None`, so a misbehaving
+// Go implementation cannot mask a failure by clearing the error but leaving
+// the reject code set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(i32)]
+pub enum RejectCode {
+    Success = 0,
+    SysFatal = 1,
+    SysTransient = 2,
+    DestinationInvalid = 3,
+    ContractReject = 4,
+    ContractError = 5,
+    /// A code the Go side returned that this version of libwasmvm does not know about.
+    Unknown = -1,
+}
+
+impl RejectCode {
+    fn from_i32(code: i32) -> Self {
+        match code {
+            0 => RejectCode::Success,
+            1 => RejectCode::SysFatal,
+            2 => RejectCode::SysTransient,
+            3 => RejectCode::DestinationInvalid,
+            4 => RejectCode::ContractReject,
+            5 => RejectCode::ContractError,
+            _ => RejectCode::Unknown,
+        }
+    }
+
+    /// Whether a caller may safely retry the call that produced this code.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, RejectCode::SysTransient)
+    }
+}
+
 // this represents something passed in from the caller side of FFI
 // in this case a struct with go function pointers
 #[repr(C)]
@@ -36,8 +80,10 @@ pub struct GoApi_vtable {
         U8SliceView,          // input: args
         bool,                 // input: is readonly
         U8SliceView,          // input: callstack
+        U8SliceView,          // input: serialized Vec<Coin> to transfer to the callee before the call
         u64,                  // input: gas limit
         *mut UnmanagedVector, // output: returned data bytes
+        *mut i32,             // output: reject code, 0 means success
         *mut UnmanagedVector, // output: error message
         *mut u64,             // output: gas used
     ) -> i32,
@@ -49,6 +95,19 @@ pub struct GoApi_vtable {
         *mut UnmanagedVector, // output: error message
         *mut u64,             // output: gas used
     ) -> i32,
+    // A one-way call: dispatches the callable point and does not wait for (or
+    // even allocate a slot for) a reply, unlike `call_callable_point`.
+    pub notify_callable_point: extern "C" fn(
+        *const api_t,
+        U8SliceView,          // input: address
+        U8SliceView,          // input: name of callable point
+        U8SliceView,          // input: args
+        bool,                 // input: is readonly
+        U8SliceView,          // input: callstack
+        u64,                  // input: gas limit
+        *mut UnmanagedVector, // output: error message
+        *mut u64,             // output: gas used
+    ) -> i32,
 }
 
 #[repr(C)]
@@ -138,10 +197,12 @@ impl BackendApi for GoApi {
         args: &[u8],
         is_readonly: bool,
         callstack: &[u8],
+        funds: &[Coin],
         gas_limit: u64,
     ) -> BackendResult<Vec<u8>> {
         let mut error_msg = UnmanagedVector::default();
         let mut result = UnmanagedVector::default();
+        let mut reject_code = 0_i32;
         let mut used_gas = 0_u64;
         let name_binary = match serde_json::to_vec(name) {
             Ok(v) => v,
@@ -155,6 +216,18 @@ impl BackendApi for GoApi {
                 )
             }
         };
+        let funds_binary = match serde_json::to_vec(funds) {
+            Ok(v) => v,
+            Err(e) => {
+                return (
+                    Err(BackendError::dynamic_link_err(format!(
+                        "Error during serializing funds for callable point {} of {}: {}",
+                        name, contract_addr, e
+                    ))),
+                    GasInfo::with_cost(0),
+                )
+            }
+        };
         let go_result: GoError = (self.vtable.call_callable_point)(
             self.state,
             U8SliceView::new(Some(contract_addr.as_bytes())),
@@ -162,18 +235,22 @@ impl BackendApi for GoApi {
             U8SliceView::new(Some(args)),
             is_readonly,
             U8SliceView::new(Some(callstack)),
+            U8SliceView::new(Some(&funds_binary)),
             gas_limit,
             &mut result as *mut UnmanagedVector,
+            &mut reject_code as *mut i32,
             &mut error_msg as *mut UnmanagedVector,
             &mut used_gas as *mut u64,
         )
         .into();
         let result = result.consume();
         let gas_info = GasInfo::with_cost(used_gas);
+        // Include the funds in the error context: a failed transfer (e.g.
+        // insufficient balance) is reported through this same path.
         let default = || {
             format!(
-                "Failed to call callable point {} of {}",
-                name, contract_addr,
+                "Failed to call callable point {} of {} with funds {:?}",
+                name, contract_addr, funds,
             )
         };
         unsafe {
@@ -188,6 +265,24 @@ impl BackendApi for GoApi {
             }
         }
 
+        // A nonzero reject code means the callee rejected the call even if the
+        // Go side reported `GoError::None`, so a misbehaving Go implementation
+        // cannot mask a failure this way.
+        let reject_code = RejectCode::from_i32(reject_code);
+        if reject_code != RejectCode::Success {
+            return (
+                Err(BackendError::dynamic_link_err(format!(
+                    r#"Callable point "{}" of contract "{}" was rejected: {:?} (retryable: {}, funds: {:?})"#,
+                    name,
+                    contract_addr,
+                    reject_code,
+                    reject_code.is_retryable(),
+                    funds,
+                ))),
+                gas_info,
+            );
+        }
+
         let result = result
             .ok_or_else(|| BackendError::unknown("Unset result"))
             .map(|data| data.to_vec());
@@ -233,6 +328,130 @@ impl BackendApi for GoApi {
             .map(|data| data.to_vec());
         (result, gas_info)
     }
+
+    /// A one-way, fire-and-forget call to a callable point (mirroring
+    /// ic-cdk's `notify`): it is dispatched and consumes gas like
+    /// `call_callable_point`, but no reply is awaited and no result
+    /// `UnmanagedVector` is allocated.
+    ///
+    /// Lives alongside `call_callable_point` in this `impl BackendApi for
+    /// GoApi` block, rather than as an inherent `GoApi` method, so generic
+    /// `A: BackendApi` callers can reach it too.
+    fn notify_callable_point(
+        &self,
+        contract_addr: &str,
+        name: &str,
+        args: &[u8],
+        is_readonly: bool,
+        callstack: &[u8],
+        gas_limit: u64,
+    ) -> BackendResult<()> {
+        let mut error_msg = UnmanagedVector::default();
+        let mut used_gas = 0_u64;
+        let name_binary = match serde_json::to_vec(name) {
+            Ok(v) => v,
+            Err(e) => {
+                return (
+                    Err(BackendError::dynamic_link_err(format!(
+                        "Error during serializing callable point's name to notify: {}",
+                        e
+                    ))),
+                    GasInfo::with_cost(0),
+                )
+            }
+        };
+        let go_result: GoError = (self.vtable.notify_callable_point)(
+            self.state,
+            U8SliceView::new(Some(contract_addr.as_bytes())),
+            U8SliceView::new(Some(&name_binary)),
+            U8SliceView::new(Some(args)),
+            is_readonly,
+            U8SliceView::new(Some(callstack)),
+            gas_limit,
+            &mut error_msg as *mut UnmanagedVector,
+            &mut used_gas as *mut u64,
+        )
+        .into();
+        let gas_info = GasInfo::with_cost(used_gas);
+        let default = || {
+            format!(
+                "Failed to notify callable point {} of {}",
+                name, contract_addr,
+            )
+        };
+        unsafe {
+            if let Err(err) = go_result.into_result(error_msg, default) {
+                return (
+                    Err(BackendError::dynamic_link_err(format!(
+                        r#"Error during notifying callable point "{}" of contract "{}": {}"#,
+                        name, contract_addr, err
+                    ))),
+                    gas_info,
+                );
+            }
+        }
+
+        (Ok(()), gas_info)
+    }
+}
+
+impl GoApi {
+    /// A typed convenience wrapper around [`BackendApi::call_callable_point`].
+    ///
+    /// `args` is JSON-encoded as a positional array matching the callable
+    /// point's parameter list, and the raw reply bytes are decoded into `R`.
+    /// The raw byte API underneath is untouched; this only adds
+    /// serialization.
+    pub fn call_callable_point_typed<A, R>(
+        &self,
+        contract_addr: &str,
+        name: &str,
+        args: A,
+        is_readonly: bool,
+        callstack: &[u8],
+        funds: &[Coin],
+        gas_limit: u64,
+    ) -> BackendResult<R>
+    where
+        A: Serialize,
+        R: DeserializeOwned,
+    {
+        let args_bin = match serde_json::to_vec(&args) {
+            Ok(v) => v,
+            Err(e) => {
+                return (
+                    Err(BackendError::dynamic_link_err(format!(
+                        r#"Error encoding arguments for callable point "{}" of "{}": {}"#,
+                        name, contract_addr, e
+                    ))),
+                    GasInfo::with_cost(0),
+                )
+            }
+        };
+
+        let (result, gas_info) = self.call_callable_point(
+            contract_addr,
+            name,
+            &args_bin,
+            is_readonly,
+            callstack,
+            funds,
+            gas_limit,
+        );
+
+        let result = result.and_then(|data| {
+            serde_json::from_slice(&data).map_err(|e| {
+                BackendError::dynamic_link_err(format!(
+                    r#"Error decoding result of callable point "{}" of "{}" as {}: {}"#,
+                    name,
+                    contract_addr,
+                    std::any::type_name::<R>(),
+                    e
+                ))
+            })
+        });
+        (result, gas_info)
+    }
 }
 
 #[cfg(test)]
@@ -288,15 +507,38 @@ mod tests {
         _args: U8SliceView,
         _is_readonly: bool,
         _callstack: U8SliceView,
+        _funds: U8SliceView,
         _gas_limit: u64,
         _result: *mut UnmanagedVector,
+        reject_code: *mut i32,
         _err: *mut UnmanagedVector,
         _gas_used: *mut u64,
     ) -> i32 {
+        unsafe { *reject_code = RejectCode::Success as i32 };
         // ok
         0
     }
 
+    #[no_mangle]
+    extern "C" fn mock_call_callable_point_rejected(
+        _api: *const api_t,
+        _addr: U8SliceView,
+        _name: U8SliceView,
+        _args: U8SliceView,
+        _is_readonly: bool,
+        _callstack: U8SliceView,
+        _funds: U8SliceView,
+        _gas_limit: u64,
+        _result: *mut UnmanagedVector,
+        reject_code: *mut i32,
+        _err: *mut UnmanagedVector,
+        _gas_used: *mut u64,
+    ) -> i32 {
+        unsafe { *reject_code = RejectCode::ContractReject as i32 };
+        // ok, but the call itself was rejected by the callee
+        0
+    }
+
     #[no_mangle]
     extern "C" fn mock_validate_interface(
         _api: *const api_t,
@@ -310,6 +552,22 @@ mod tests {
         0
     }
 
+    #[no_mangle]
+    extern "C" fn mock_notify_callable_point(
+        _api: *const api_t,
+        _addr: U8SliceView,
+        _name: U8SliceView,
+        _args: U8SliceView,
+        _is_readonly: bool,
+        _callstack: U8SliceView,
+        _gas_limit: u64,
+        _err: *mut UnmanagedVector,
+        _gas_used: *mut u64,
+    ) -> i32 {
+        // ok
+        0
+    }
+
     #[test]
     fn test_canonical_address() {
         let mock_go_api_vtable = GoApi_vtable {
@@ -317,6 +575,7 @@ mod tests {
             canonicalize_address: mock_address,
             call_callable_point: mock_call_callable_point,
             validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
         };
 
         let mock_go_api = GoApi {
@@ -336,6 +595,7 @@ mod tests {
             canonicalize_address: mock_address_panic,
             call_callable_point: mock_call_callable_point,
             validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
         };
 
         let mock_go_api = GoApi {
@@ -356,6 +616,7 @@ mod tests {
             canonicalize_address: mock_address_with_none_output,
             call_callable_point: mock_call_callable_point,
             validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
         };
 
         let mock_go_api = GoApi {
@@ -375,6 +636,7 @@ mod tests {
             canonicalize_address: mock_address,
             call_callable_point: mock_call_callable_point,
             validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
         };
 
         let mock_go_api = GoApi {
@@ -394,6 +656,7 @@ mod tests {
             canonicalize_address: mock_address,
             call_callable_point: mock_call_callable_point,
             validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
         };
 
         let mock_go_api = GoApi {
@@ -413,6 +676,7 @@ mod tests {
             canonicalize_address: mock_address,
             call_callable_point: mock_call_callable_point,
             validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
         };
 
         let mock_go_api = GoApi {
@@ -423,4 +687,176 @@ mod tests {
         let (canonical_address, _) = mock_go_api.human_address(b"canonical");
         canonical_address.unwrap();
     }
+
+    #[test]
+    fn test_call_callable_point_surfaces_reject_code() {
+        let mock_go_api_vtable = GoApi_vtable {
+            humanize_address: mock_address,
+            canonicalize_address: mock_address,
+            call_callable_point: mock_call_callable_point_rejected,
+            validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
+        };
+
+        let mock_go_api = GoApi {
+            state: &C_API_T as *const _,
+            vtable: mock_go_api_vtable,
+        };
+
+        let (result, _) = mock_go_api.call_callable_point(
+            "contract_addr",
+            "callable_point",
+            &[],
+            false,
+            &[],
+            &[],
+            0,
+        );
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("ContractReject"));
+        assert!(err.contains("retryable: false"));
+    }
+
+    #[test]
+    fn test_call_callable_point_insufficient_balance_rejection_reports_funds() {
+        let mock_go_api_vtable = GoApi_vtable {
+            humanize_address: mock_address,
+            canonicalize_address: mock_address,
+            call_callable_point: mock_call_callable_point_rejected,
+            validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
+        };
+
+        let mock_go_api = GoApi {
+            state: &C_API_T as *const _,
+            vtable: mock_go_api_vtable,
+        };
+
+        let funds = vec![Coin::new(1_000_000_000, "ucosm")];
+        let (result, _) = mock_go_api.call_callable_point(
+            "contract_addr",
+            "callable_point",
+            &[],
+            false,
+            &[],
+            &funds,
+            0,
+        );
+        let err = result.unwrap_err().to_string();
+        // The transfer and the invocation are one unit: a reject (e.g. from
+        // insufficient balance on the caller's side) must name the funds that
+        // were attempted to move, not just the callable point.
+        assert!(err.contains("ucosm"));
+        assert!(err.contains("1000000000"));
+    }
+
+    #[test]
+    fn test_reject_code_is_retryable() {
+        assert!(!RejectCode::Success.is_retryable());
+        assert!(RejectCode::SysTransient.is_retryable());
+        assert!(!RejectCode::SysFatal.is_retryable());
+        assert!(!RejectCode::DestinationInvalid.is_retryable());
+        assert!(!RejectCode::ContractReject.is_retryable());
+        assert!(!RejectCode::ContractError.is_retryable());
+        assert_eq!(RejectCode::from_i32(42), RejectCode::Unknown);
+    }
+
+    #[no_mangle]
+    extern "C" fn mock_call_callable_point_returning_u32(
+        _api: *const api_t,
+        _addr: U8SliceView,
+        _name: U8SliceView,
+        _args: U8SliceView,
+        _is_readonly: bool,
+        _callstack: U8SliceView,
+        _funds: U8SliceView,
+        _gas_limit: u64,
+        result: *mut UnmanagedVector,
+        reject_code: *mut i32,
+        _err: *mut UnmanagedVector,
+        _gas_used: *mut u64,
+    ) -> i32 {
+        unsafe {
+            *reject_code = RejectCode::Success as i32;
+            *result = UnmanagedVector::new(Some(serde_json::to_vec(&42u32).unwrap()));
+        }
+        0
+    }
+
+    #[test]
+    fn test_call_callable_point_typed_decodes_result() {
+        let mock_go_api_vtable = GoApi_vtable {
+            humanize_address: mock_address,
+            canonicalize_address: mock_address,
+            call_callable_point: mock_call_callable_point_returning_u32,
+            validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
+        };
+
+        let mock_go_api = GoApi {
+            state: &C_API_T as *const _,
+            vtable: mock_go_api_vtable,
+        };
+
+        let (result, _) = mock_go_api.call_callable_point_typed::<_, u32>(
+            "contract_addr",
+            "callable_point",
+            ("arg1", 2u32),
+            false,
+            &[],
+            &[],
+            0,
+        );
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn test_call_callable_point_typed_decode_mismatch_is_an_error() {
+        let mock_go_api_vtable = GoApi_vtable {
+            humanize_address: mock_address,
+            canonicalize_address: mock_address,
+            call_callable_point: mock_call_callable_point_returning_u32,
+            validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
+        };
+
+        let mock_go_api = GoApi {
+            state: &C_API_T as *const _,
+            vtable: mock_go_api_vtable,
+        };
+
+        // The callee returned a plain u32, not a (String, bool) tuple.
+        let (result, _) = mock_go_api.call_callable_point_typed::<_, (String, bool)>(
+            "contract_addr",
+            "callable_point",
+            (),
+            false,
+            &[],
+            &[],
+            0,
+        );
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("callable_point"));
+        assert!(err.contains("contract_addr"));
+    }
+
+    #[test]
+    fn test_notify_callable_point() {
+        let mock_go_api_vtable = GoApi_vtable {
+            humanize_address: mock_address,
+            canonicalize_address: mock_address,
+            call_callable_point: mock_call_callable_point,
+            validate_interface: mock_validate_interface,
+            notify_callable_point: mock_notify_callable_point,
+        };
+
+        let mock_go_api = GoApi {
+            state: &C_API_T as *const _,
+            vtable: mock_go_api_vtable,
+        };
+
+        let (result, _) =
+            mock_go_api.notify_callable_point("contract_addr", "callable_point", &[], false, &[], 0);
+        assert!(result.is_ok());
+    }
 }
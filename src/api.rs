@@ -1,10 +1,13 @@
+use cosmwasm_std::{Addr, Coin};
 use cosmwasm_vm::{
     copy_region_vals_between_env, write_value_to_env, Backend, BackendApi, BackendError,
     BackendResult, Checksum, Environment, FunctionMetadata, GasInfo, InstanceOptions, Querier,
     Storage, WasmerVal,
 };
+use serde::Serialize;
 use std::convert::TryInto;
 use std::mem::MaybeUninit;
+use std::sync::{Arc, Mutex};
 use wasmer::Module;
 
 use crate::cache::{cache_t, to_cache};
@@ -14,6 +17,67 @@ use crate::memory::{U8SliceView, UnmanagedVector};
 use crate::querier::GoQuerier;
 use crate::storage::GoStorage;
 
+/// One structured record of a single `contract_call` invocation, in the
+/// spirit of the EVM's `Tracer`/`VMTracer` externalities: enough on its own
+/// to reconstruct the call graph and per-call gas cost of a dynamic-link
+/// execution after the fact.
+#[derive(Clone, Debug, Serialize)]
+pub struct CallTraceRecord {
+    pub caller_checksum: Option<Vec<u8>>,
+    pub callee_checksum: Vec<u8>,
+    pub contract_addr: String,
+    pub function_name: String,
+    pub gas_limit: u64,
+    pub gas_used_internally: u64,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Receives a [`CallTraceRecord`] for every `contract_call` made while it is
+/// attached to an `Environment`/`InstanceOptions`. Implementations must be
+/// cheap enough to call from the hot path and safe to share across the
+/// nested calls a dynamic link can make.
+pub trait CallTracer: Send + Sync {
+    fn record(&self, record: CallTraceRecord);
+}
+
+/// A tracer that discards every record, so attaching it behaves the same as
+/// attaching no tracer (`call_tracer: None`), which is `contract_call`'s
+/// previous behavior.
+#[derive(Default)]
+pub struct NoopCallTracer;
+
+impl CallTracer for NoopCallTracer {
+    fn record(&self, _record: CallTraceRecord) {}
+}
+
+/// Keeps every record it is given in memory, for callers that want to build
+/// call-graph or gas-profiling tools over dynamic linking.
+#[derive(Default)]
+pub struct CollectingCallTracer {
+    records: Mutex<Vec<CallTraceRecord>>,
+}
+
+impl CollectingCallTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drains the collected records and hands them back JSON-encoded, ready
+    /// to cross the FFI boundary as an `UnmanagedVector`.
+    pub fn take_records(&self) -> UnmanagedVector {
+        let records = std::mem::take(&mut *self.records.lock().unwrap());
+        let data = serde_json::to_vec(&records).unwrap_or_default();
+        UnmanagedVector::new(Some(data))
+    }
+}
+
+impl CallTracer for CollectingCallTracer {
+    fn record(&self, record: CallTraceRecord) {
+        self.records.lock().unwrap().push(record);
+    }
+}
+
 // this represents something passed in from the caller side of FFI
 // in this case a struct with go function pointers
 #[repr(C)]
@@ -51,6 +115,24 @@ pub struct GoApi_vtable {
         *mut UnmanagedVector, // error message output
         *mut u64,
     ) -> i32,
+    // Deterministically derives a fresh child contract address for `checksum`,
+    // registers it with the chain, and hands back an env for it (the same
+    // shape `get_contract_env` returns) so the Rust side can instantiate and
+    // run the child's `instantiate` entry point itself, mirroring EVM's
+    // `create(gas, endowment, init_code) -> (gas_left, Option<Address>)`.
+    pub create_contract: extern "C" fn(
+        *const api_t,
+        U8SliceView,          // input: code checksum of the child contract
+        U8SliceView,          // input: label for the new contract
+        U8SliceView,          // input: serialized Vec<Coin> endowment
+        *mut UnmanagedVector, // output: new contract address
+        *mut UnmanagedVector, // output: env for the new contract
+        *mut *mut cache_t,
+        *mut Db,
+        *mut GoQuerier,
+        *mut UnmanagedVector, // output: error message
+        *mut u64,             // output: gas used
+    ) -> i32,
 }
 
 #[repr(C)]
@@ -67,6 +149,29 @@ pub struct GoApi {
 // see: https://stackoverflow.com/questions/50258359/can-a-struct-containing-a-raw-pointer-implement-send-and-be-ffi-safe
 unsafe impl Send for GoApi {}
 
+// Recursion depth is bounded by the caller's own
+// `InstanceOptions::max_call_depth` (read via `caller_env.max_call_depth()`
+// below), not a fixed constant, so a callee can never end up with a looser
+// bound than its caller. Enforced independently of gas, since a tight cycle
+// of cheap calls can overflow the native stack before it runs out of gas.
+
+/// Distinguishes the three ways one contract's code can be invoked from
+/// another, mirroring the CALL / DELEGATECALL / STATICCALL distinction
+/// familiar from the EVM.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    /// Run the callee's code against the callee's own storage, querier and
+    /// env. This is the original `contract_call` behavior.
+    Call,
+    /// Run the callee's code against the *caller's* storage, querier and
+    /// env, so the callee executes as if it were part of the caller.
+    Delegate,
+    /// Run the callee's code read-only, regardless of the caller's own
+    /// storage access mode. Any write attempted by the callee surfaces as
+    /// an ordinary call error.
+    Static,
+}
+
 impl BackendApi for GoApi {
     fn canonical_address(&self, human: &str) -> BackendResult<Vec<u8>> {
         let mut output = UnmanagedVector::default();
@@ -136,12 +241,48 @@ impl BackendApi for GoApi {
         contract_addr: &str,
         func_info: &FunctionMetadata,
         args: &[WasmerVal],
+        call_kind: CallKind,
+        storage_contract_addr: &str,
     ) -> BackendResult<Box<[WasmerVal]>>
     where
         A: BackendApi + 'static,
         S: Storage + 'static,
         Q: Querier + 'static,
     {
+        // Bound native recursion before doing any FFI work at all: a
+        // contract calling itself, directly or through a cycle, would
+        // otherwise recurse until the host stack overflows regardless of how
+        // much gas it has left.
+        let callstack = caller_env.dynamic_callstack();
+        let max_call_depth = caller_env.max_call_depth();
+        if callstack.len() >= max_call_depth {
+            return (
+                Err(BackendError::user_err("call depth exceeded")),
+                GasInfo::with_cost(0),
+            );
+        }
+        // Contracts opt into this via `func_info.non_reentrant`; plain calls
+        // are allowed to reenter unless the callee declares otherwise. This
+        // is deliberately opt-in, unlike `do_call_callable_point`'s
+        // unconditional reentrancy check in `dynamic_link.rs`: a
+        // `contract_call` target is an ordinary contract function that
+        // reentrancy-safe contracts already call all the time, so rejecting
+        // every cycle by default would break callers that never asked for
+        // the protection, whereas a callable point is a dedicated dynamic-link
+        // entry point that has no equivalent opt-in metadata to consult and
+        // is reentered far less often by design.
+        if func_info.non_reentrant
+            && callstack.iter().any(|addr| addr.as_str() == contract_addr)
+        {
+            return (
+                Err(BackendError::user_err(format!(
+                    "reentrant call into \"{}\" rejected",
+                    contract_addr
+                ))),
+                GasInfo::with_cost(0),
+            );
+        }
+
         let mut error_msg = UnmanagedVector::default();
         let mut contract_env_out = UnmanagedVector::default();
         let mut cache_ptr_out = MaybeUninit::uninit();
@@ -163,10 +304,13 @@ impl BackendApi for GoApi {
         )
         .into();
         let mut gas_info = GasInfo::with_cost(used_gas);
-        let gas_limit = match caller_env.get_gas_left().checked_sub(used_gas) {
-            Some(renaming) => renaming,
-            None => return (Err(BackendError::out_of_gas()), gas_info),
-        };
+        // Final `gas_limit` is computed below once `gas_info.cost` reflects
+        // every `get_contract_env` fetch this call makes (a delegatecall
+        // makes a second one); bail out early here if even the first fetch
+        // alone already exceeds what the caller has left.
+        if caller_env.get_gas_left().checked_sub(used_gas).is_none() {
+            return (Err(BackendError::out_of_gas()), gas_info);
+        }
 
         // return complete error message (reading from buffer for GoResult::Other)
         let default = || {
@@ -181,40 +325,138 @@ impl BackendApi for GoApi {
             }
         }
 
-        let contract_env = match contract_env_out.consume() {
-            Some(c) => c,
-            None => return (Err(BackendError::unknown("invalid contract env")), gas_info),
+        let checksum: Checksum = match checksum_out.consume() {
+            Some(c) => c.as_slice().try_into().unwrap(),
+            None => return (Err(BackendError::unknown("invalid checksum")), gas_info),
         };
 
-        let cache_ptr = unsafe { cache_ptr_out.assume_init() };
-        let db = unsafe { db_out.assume_init() };
-        let querier = unsafe { querier_out.assume_init() };
+        // For a plain call/staticcall, code, storage and env all come from
+        // `contract_addr`. For a delegatecall, the code still comes from
+        // `contract_addr` but the storage, querier and env context come from
+        // `storage_contract_addr` (the caller), so the callee's code runs
+        // against the caller's own state rather than its own.
+        let (contract_env, cache_ptr, db, querier) = match call_kind {
+            CallKind::Call | CallKind::Static => {
+                let contract_env = match contract_env_out.consume() {
+                    Some(c) => c,
+                    None => {
+                        return (Err(BackendError::unknown("invalid contract env")), gas_info)
+                    }
+                };
+                let cache_ptr = unsafe { cache_ptr_out.assume_init() };
+                let db = unsafe { db_out.assume_init() };
+                let querier = unsafe { querier_out.assume_init() };
+                (contract_env, cache_ptr, db, querier)
+            }
+            CallKind::Delegate => {
+                // The env/db/querier fetched above belonged to `contract_addr`
+                // and are discarded; only its checksum (the code) is kept.
+                let _ = contract_env_out.consume();
+                let _ = unsafe { cache_ptr_out.assume_init() };
+                let _ = unsafe { db_out.assume_init() };
+                let _ = unsafe { querier_out.assume_init() };
+
+                let mut storage_error_msg = UnmanagedVector::default();
+                let mut storage_env_out = UnmanagedVector::default();
+                let mut storage_cache_ptr_out = MaybeUninit::uninit();
+                let mut storage_db_out = MaybeUninit::uninit();
+                let mut storage_querier_out = MaybeUninit::uninit();
+                let mut storage_checksum_out = UnmanagedVector::default();
+                let mut storage_used_gas = 0_u64;
+
+                let storage_go_result: GoResult = (self.vtable.get_contract_env)(
+                    self.state,
+                    U8SliceView::new(Some(storage_contract_addr.as_bytes())),
+                    &mut storage_env_out as *mut UnmanagedVector,
+                    storage_cache_ptr_out.as_mut_ptr(),
+                    storage_db_out.as_mut_ptr(),
+                    storage_querier_out.as_mut_ptr(),
+                    &mut storage_checksum_out as *mut UnmanagedVector,
+                    &mut storage_error_msg as *mut UnmanagedVector,
+                    &mut storage_used_gas as *mut u64,
+                )
+                .into();
+                gas_info.cost += storage_used_gas;
+                let storage_default = || {
+                    format!(
+                        "Failed delegatecall storage lookup for: {}",
+                        hex::encode_upper(storage_contract_addr)
+                    )
+                };
+                unsafe {
+                    if let Err(err) =
+                        storage_go_result.into_ffi_result(storage_error_msg, storage_default)
+                    {
+                        return (Err(err), gas_info);
+                    }
+                }
+
+                let storage_env = match storage_env_out.consume() {
+                    Some(c) => c,
+                    None => {
+                        return (Err(BackendError::unknown("invalid contract env")), gas_info)
+                    }
+                };
+                let storage_cache_ptr = unsafe { storage_cache_ptr_out.assume_init() };
+                let storage_db = unsafe { storage_db_out.assume_init() };
+                let storage_querier = unsafe { storage_querier_out.assume_init() };
+                let _ = storage_checksum_out.consume();
+                (storage_env, storage_cache_ptr, storage_db, storage_querier)
+            }
+        };
+
+        // For a delegatecall, `gas_info.cost` just grew by `storage_used_gas`
+        // from the second `get_contract_env` fetch above; the callee's gas
+        // limit has to account for that too, or it could be handed more
+        // headroom than the caller actually has left.
+        let gas_limit = match caller_env.get_gas_left().checked_sub(gas_info.cost) {
+            Some(remaining) => remaining,
+            None => return (Err(BackendError::out_of_gas()), gas_info),
+        };
 
         let cache = match to_cache(cache_ptr) {
             Some(c) => c,
             None => return (Err(BackendError::unknown("failed to_cache")), gas_info),
         };
-
-        let checksum: Checksum = match checksum_out.consume() {
-            Some(c) => c.as_slice().try_into().unwrap(),
-            None => return (Err(BackendError::unknown("invalid checksum")), gas_info),
-        };
+        // Keep a handle to the callee's `Db` so it can be rolled back after
+        // it has been moved into the backend below.
+        let db_handle = db;
         let backend = into_backend(db, *self, querier);
 
         let print_debug = false;
+        let call_tracer = caller_env.call_tracer();
         let options = InstanceOptions {
             gas_limit,
             print_debug,
+            // Propagate the same depth bound the caller itself was
+            // configured with, rather than a fixed constant, so the callee
+            // can't end up with a looser bound than its caller.
+            max_call_depth,
+            call_tracer: call_tracer.clone(),
         };
         let mut callee_instance = match cache.get_instance(&checksum, backend, options) {
             Ok(ins) => ins,
             Err(e) => return (Err(BackendError::unknown(e.to_string())), gas_info),
         };
         callee_instance.env.set_serialized_env(&contract_env);
-        callee_instance.set_storage_readonly(caller_env.is_storage_readonly());
+        // A staticcall is read-only no matter what the caller's own state is;
+        // a normal call or delegatecall inherits the caller's readonly-ness.
+        callee_instance.set_storage_readonly(match call_kind {
+            CallKind::Static => true,
+            CallKind::Call | CallKind::Delegate => caller_env.is_storage_readonly(),
+        });
+
+        // Take a savepoint before anything the callee does can touch
+        // storage. Nested `contract_call`s stack checkpoints, so a deep call
+        // tree unwinds one frame at a time when an inner call fails.
+        let checkpoint = db_handle.checkpoint();
+
         match caller_env.try_pass_callstack(&mut callee_instance.env) {
             Ok(_) => {}
-            Err(e) => return (Err(BackendError::user_err(e.to_string())), gas_info),
+            Err(e) => {
+                db_handle.revert_to_checkpoint(checkpoint);
+                return (Err(BackendError::user_err(e.to_string())), gas_info);
+            }
         }
 
         let env_arg_region_ptr = write_value_to_env(&callee_instance.env, &contract_env).unwrap();
@@ -231,12 +473,202 @@ impl BackendApi for GoApi {
             &arg_region_ptrs,
         ) {
             Ok(rets) => {
+                db_handle.discard_checkpoint(checkpoint);
                 Ok(
                     copy_region_vals_between_env(&callee_instance.env, caller_env, &rets, true)
                         .unwrap(),
                 )
             }
-            Err(e) => Err(BackendError::dynamic_link_err(e.to_string())),
+            // A write attempted under a staticcall's forced read-only storage
+            // surfaces here as an ordinary callee error.
+            Err(e) => {
+                db_handle.revert_to_checkpoint(checkpoint);
+                Err(BackendError::dynamic_link_err(e.to_string()))
+            }
+        };
+        let gas_used_internally = callee_instance.create_gas_report().used_internally;
+        gas_info.cost += gas_used_internally;
+
+        if let Some(tracer) = call_tracer {
+            tracer.record(CallTraceRecord {
+                caller_checksum: caller_env.checksum().map(Into::into),
+                callee_checksum: checksum.into(),
+                contract_addr: contract_addr.to_string(),
+                function_name: func_info.name.clone(),
+                gas_limit,
+                gas_used_internally,
+                success: call_ret.is_ok(),
+                error: call_ret.as_ref().err().map(|e| e.to_string()),
+            });
+        }
+
+        (call_ret, gas_info)
+    }
+
+    /// Instantiates a brand-new child contract from `checksum` during dynamic
+    /// linking, the analog of EVM externalities' `create`: the Go side
+    /// deterministically derives the child's address and registers it with
+    /// the chain, and this method then runs the child's `instantiate` entry
+    /// point the same way `contract_call` runs an arbitrary callee function.
+    fn create_contract<A, S, Q>(
+        &self,
+        caller_env: &Environment<A, S, Q>,
+        checksum: Checksum,
+        label: &str,
+        endowment: &[Coin],
+        instantiate_info: &FunctionMetadata,
+        init_msg: &[WasmerVal],
+    ) -> BackendResult<(Addr, Box<[WasmerVal]>)>
+    where
+        A: BackendApi + 'static,
+        S: Storage + 'static,
+        Q: Querier + 'static,
+    {
+        // Spawning a child contract still nests a call frame, so it counts
+        // against the same depth bound as `contract_call`.
+        let max_call_depth = caller_env.max_call_depth();
+        if caller_env.dynamic_callstack().len() >= max_call_depth {
+            return (
+                Err(BackendError::user_err("call depth exceeded")),
+                GasInfo::with_cost(0),
+            );
+        }
+
+        let mut error_msg = UnmanagedVector::default();
+        let mut address_out = UnmanagedVector::default();
+        let mut contract_env_out = UnmanagedVector::default();
+        let mut cache_ptr_out = MaybeUninit::uninit();
+        let mut db_out = MaybeUninit::uninit();
+        let mut querier_out = MaybeUninit::uninit();
+        let mut used_gas = 0_u64;
+
+        let endowment_binary = match serde_json::to_vec(endowment) {
+            Ok(v) => v,
+            Err(e) => {
+                return (
+                    Err(BackendError::dynamic_link_err(format!(
+                        "Error serializing endowment for new contract \"{}\": {}",
+                        label, e
+                    ))),
+                    GasInfo::with_cost(0),
+                )
+            }
+        };
+
+        let checksum_binary: Vec<u8> = checksum.clone().into();
+        let go_result: GoResult = (self.vtable.create_contract)(
+            self.state,
+            U8SliceView::new(Some(&checksum_binary)),
+            U8SliceView::new(Some(label.as_bytes())),
+            U8SliceView::new(Some(&endowment_binary)),
+            &mut address_out as *mut UnmanagedVector,
+            &mut contract_env_out as *mut UnmanagedVector,
+            cache_ptr_out.as_mut_ptr(),
+            db_out.as_mut_ptr(),
+            querier_out.as_mut_ptr(),
+            &mut error_msg as *mut UnmanagedVector,
+            &mut used_gas as *mut u64,
+        )
+        .into();
+        let mut gas_info = GasInfo::with_cost(used_gas);
+        let gas_limit = match caller_env.get_gas_left().checked_sub(used_gas) {
+            Some(remaining) => remaining,
+            None => return (Err(BackendError::out_of_gas()), gas_info),
+        };
+
+        let default = || format!("Failed to create contract \"{}\"", label);
+        unsafe {
+            if let Err(err) = go_result.into_ffi_result(error_msg, default) {
+                return (Err(err), gas_info);
+            }
+        }
+
+        let address = match address_out.consume() {
+            Some(a) => match String::from_utf8(a) {
+                Ok(s) => Addr::unchecked(s),
+                Err(e) => return (Err(BackendError::from(e)), gas_info),
+            },
+            None => {
+                return (
+                    Err(BackendError::unknown("invalid new contract address")),
+                    gas_info,
+                )
+            }
+        };
+
+        let contract_env = match contract_env_out.consume() {
+            Some(c) => c,
+            None => return (Err(BackendError::unknown("invalid contract env")), gas_info),
+        };
+
+        let cache_ptr = unsafe { cache_ptr_out.assume_init() };
+        let db = unsafe { db_out.assume_init() };
+        let querier = unsafe { querier_out.assume_init() };
+
+        let cache = match to_cache(cache_ptr) {
+            Some(c) => c,
+            None => return (Err(BackendError::unknown("failed to_cache")), gas_info),
+        };
+        // Keep a handle to the child's `Db` so its instantiate run can be
+        // rolled back after it has been moved into the backend below, same
+        // as `contract_call` does for an ordinary callee.
+        let db_handle = db;
+        let backend = into_backend(db, *self, querier);
+
+        let print_debug = false;
+        let options = InstanceOptions {
+            gas_limit,
+            print_debug,
+            // Same depth bound the caller was configured with, so a child
+            // contract can't be spawned with a looser one.
+            max_call_depth,
+            // `create_contract` itself isn't traced, but the child contract
+            // may go on to make its own `contract_call`s, so its tracer
+            // should still be the caller's.
+            call_tracer: caller_env.call_tracer(),
+        };
+        let mut callee_instance = match cache.get_instance(&checksum, backend, options) {
+            Ok(ins) => ins,
+            Err(e) => return (Err(BackendError::unknown(e.to_string())), gas_info),
+        };
+        callee_instance.env.set_serialized_env(&contract_env);
+
+        // Take a savepoint before `instantiate` can touch storage, so a
+        // child contract that traps mid-instantiate doesn't keep whatever
+        // writes it made before failing.
+        let checkpoint = db_handle.checkpoint();
+
+        match caller_env.try_pass_callstack(&mut callee_instance.env) {
+            Ok(_) => {}
+            Err(e) => {
+                db_handle.revert_to_checkpoint(checkpoint);
+                return (Err(BackendError::user_err(e.to_string())), gas_info);
+            }
+        }
+
+        let env_arg_region_ptr = write_value_to_env(&callee_instance.env, &contract_env).unwrap();
+        let mut copied_region_ptrs: Vec<WasmerVal> =
+            copy_region_vals_between_env(caller_env, &callee_instance.env, init_msg, false)
+                .unwrap()
+                .into();
+        let mut arg_region_ptrs = vec![env_arg_region_ptr];
+        arg_region_ptrs.append(&mut copied_region_ptrs);
+
+        let call_ret = match callee_instance.call_function_strict(
+            &instantiate_info.signature,
+            &instantiate_info.name,
+            &arg_region_ptrs,
+        ) {
+            Ok(rets) => {
+                db_handle.discard_checkpoint(checkpoint);
+                let rets = copy_region_vals_between_env(&callee_instance.env, caller_env, &rets, true)
+                    .unwrap();
+                Ok((address, rets))
+            }
+            Err(e) => {
+                db_handle.revert_to_checkpoint(checkpoint);
+                Err(BackendError::dynamic_link_err(e.to_string()))
+            }
         };
         gas_info.cost += callee_instance.create_gas_report().used_internally;
 
@@ -354,6 +786,24 @@ mod tests {
         0
     }
 
+    #[no_mangle]
+    extern "C" fn mock_create_contract(
+        _api: *const api_t,
+        _checksum: U8SliceView,
+        _label: U8SliceView,
+        _endowment: U8SliceView,
+        _address: *mut UnmanagedVector,
+        _env: *mut UnmanagedVector,
+        _cache: *mut *mut cache_t,
+        _db: *mut Db,
+        _go_querier: *mut GoQuerier,
+        _err: *mut UnmanagedVector,
+        _gas_used: *mut u64,
+    ) -> i32 {
+        // ok
+        0
+    }
+
     #[no_mangle]
     extern "C" fn mock_get_contract_env_with_none_outputs(
         _api: *const api_t,
@@ -412,6 +862,7 @@ mod tests {
             humanize_address: mock_address,
             canonicalize_address: mock_address,
             get_contract_env: mock_get_contract_env_with_none_outputs,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -430,6 +881,7 @@ mod tests {
             humanize_address: mock_address,
             canonicalize_address: mock_address_panic,
             get_contract_env: mock_get_contract_env_with_none_outputs,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -449,6 +901,7 @@ mod tests {
             humanize_address: mock_address,
             canonicalize_address: mock_address_with_none_output,
             get_contract_env: mock_get_contract_env_with_none_outputs,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -467,6 +920,7 @@ mod tests {
             humanize_address: mock_address,
             canonicalize_address: mock_address,
             get_contract_env: mock_get_contract_env_with_none_outputs,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -485,6 +939,7 @@ mod tests {
             humanize_address: mock_address_panic,
             canonicalize_address: mock_address,
             get_contract_env: mock_get_contract_env_with_none_outputs,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -503,6 +958,7 @@ mod tests {
             humanize_address: mock_address_with_none_output,
             canonicalize_address: mock_address,
             get_contract_env: mock_get_contract_env_with_none_outputs,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -521,6 +977,7 @@ mod tests {
             humanize_address: mock_address,
             canonicalize_address: mock_address,
             get_contract_env: mock_get_contract_env_panic,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -540,6 +997,7 @@ mod tests {
             humanize_address: mock_address,
             canonicalize_address: mock_address,
             get_contract_env: mock_get_contract_env_with_checksum,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -559,6 +1017,7 @@ mod tests {
             humanize_address: mock_address,
             canonicalize_address: mock_address,
             get_contract_env: mock_get_contract_env_with_none_outputs,
+            create_contract: mock_create_contract,
         };
 
         let mock_go_api = GoApi {
@@ -570,4 +1029,30 @@ mod tests {
 
         module.unwrap();
     }
+
+    #[test]
+    fn collecting_call_tracer_records_and_drains() {
+        let tracer = CollectingCallTracer::new();
+        tracer.record(CallTraceRecord {
+            caller_checksum: None,
+            callee_checksum: vec![1, 2, 3],
+            contract_addr: "callee".to_string(),
+            function_name: "do_thing".to_string(),
+            gas_limit: 1_000,
+            gas_used_internally: 42,
+            success: true,
+            error: None,
+        });
+
+        let records: serde_json::Value =
+            serde_json::from_slice(&tracer.take_records().consume().unwrap()).unwrap();
+        assert_eq!(records[0]["contract_addr"], "callee");
+        assert_eq!(records[0]["gas_used_internally"], 42);
+        assert_eq!(records.as_array().unwrap().len(), 1);
+
+        // Draining once empties it; a second drain sees nothing new.
+        let records_after_drain: serde_json::Value =
+            serde_json::from_slice(&tracer.take_records().consume().unwrap()).unwrap();
+        assert!(records_after_drain.as_array().unwrap().is_empty());
+    }
 }
@@ -3,6 +3,7 @@ use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::str::from_utf8;
 
 use cosmwasm_vm::{features_from_csv, Cache, CacheOptions, Checksum, Size};
+use serde_json::{from_slice, to_vec};
 
 use crate::api::GoApi;
 use crate::args::{CACHE_ARG, CHECKSUM_ARG, DATA_DIR_ARG, FEATURES_ARG, WASM_ARG};
@@ -192,53 +193,157 @@ fn do_unpin(
     Ok(())
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Default, Debug, PartialEq)]
+/// Pins every checksum in `checksums` (a serialized `Vec<Checksum>`) in one
+/// FFI round trip, instead of chain startup paying a separate FFI call per
+/// contract for possibly hundreds of pinned contracts. This is a plain loop
+/// over the existing single-item `Cache::pin`, so it does not reduce the
+/// number of internal cache-lock acquisitions, only how many times execution
+/// crosses the FFI boundary.
+///
+/// Returns a serialized `Vec<Option<String>>`, one entry per input checksum
+/// in order: `None` on success, `Some(message)` if that checksum failed to
+/// pin. A failure on one checksum does not stop the rest from being
+/// attempted.
+#[no_mangle]
+pub extern "C" fn pin_batch(
+    cache: *mut cache_t,
+    checksums: ByteSliceView,
+    error_msg: Option<&mut UnmanagedVector>,
+) -> UnmanagedVector {
+    let r = match to_cache(cache) {
+        Some(c) => catch_unwind(AssertUnwindSafe(move || do_pin_batch(c, checksums)))
+            .unwrap_or_else(|_| Err(Error::panic())),
+        None => Err(Error::unset_arg(CACHE_ARG)),
+    };
+    let data = handle_c_error_binary(r, error_msg);
+    UnmanagedVector::new(Some(data))
+}
+
+fn do_pin_batch(
+    cache: &mut Cache<GoApi, GoStorage, GoQuerier>,
+    checksums: ByteSliceView,
+) -> Result<Vec<u8>, Error> {
+    let checksums: Vec<Checksum> = from_slice(
+        &checksums
+            .read()
+            .ok_or_else(|| Error::unset_arg(CHECKSUM_ARG))?,
+    )?;
+    // Reserve the output up front instead of growing it one push at a time.
+    let mut results: Vec<Option<String>> = Vec::with_capacity(checksums.len());
+    for checksum in &checksums {
+        results.push(match cache.pin(checksum) {
+            Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        });
+    }
+    Ok(to_vec(&results)?)
+}
+
+// `required_features`/`entrypoints` are CSV strings, mirroring the CSV
+// convention `features_from_csv` already uses for `init_cache`'s
+// `supported_features` argument, rather than a `Vec<String>`.
+// `min_memory_pages`/`max_memory_pages` are in 64 KiB wasm pages;
+// `max_memory_pages == 0` means the module declares no upper bound.
+//
+// This struct never crosses the FFI boundary by value — both `analyze_code`
+// and `analyze_code_batch` serialize it into an `UnmanagedVector`, the same
+// way `CallTraceRecord` does in `src/api.rs`, since a `String` field isn't a
+// valid `#[repr(C)]` by-value return: Go/cgo can't parse or free a Rust
+// `String`'s internal representation.
+#[derive(Clone, Default, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct AnalysisReport {
     pub has_ibc_entry_points: bool,
+    pub required_features: String,
+    pub entrypoints: String,
+    pub min_memory_pages: u32,
+    pub max_memory_pages: u32,
 }
 
 impl From<cosmwasm_vm::AnalysisReport> for AnalysisReport {
     fn from(report: cosmwasm_vm::AnalysisReport) -> Self {
         AnalysisReport {
             has_ibc_entry_points: report.has_ibc_entry_points,
+            required_features: report
+                .required_features
+                .into_iter()
+                .collect::<Vec<_>>()
+                .join(","),
+            entrypoints: report.entrypoints.into_iter().collect::<Vec<_>>().join(","),
+            min_memory_pages: report.min_memory_pages,
+            max_memory_pages: report.max_memory_pages.unwrap_or(0),
         }
     }
 }
 
+/// Returns a serialized `AnalysisReport` for `checksum`.
 #[no_mangle]
 pub extern "C" fn analyze_code(
     cache: *mut cache_t,
     checksum: ByteSliceView,
     error_msg: Option<&mut UnmanagedVector>,
-) -> AnalysisReport {
+) -> UnmanagedVector {
     let r = match to_cache(cache) {
         Some(c) => catch_unwind(AssertUnwindSafe(move || do_analyze_code(c, checksum)))
             .unwrap_or_else(|_| Err(Error::panic())),
         None => Err(Error::unset_arg(CACHE_ARG)),
     };
-    match r {
-        Ok(value) => {
-            clear_error();
-            value
-        }
-        Err(error) => {
-            set_error(error, error_msg);
-            AnalysisReport::default()
-        }
-    }
+    let data = handle_c_error_binary(r, error_msg);
+    UnmanagedVector::new(Some(data))
 }
 
 fn do_analyze_code(
     cache: &mut Cache<GoApi, GoStorage, GoQuerier>,
     checksum: ByteSliceView,
-) -> Result<AnalysisReport, Error> {
+) -> Result<Vec<u8>, Error> {
     let checksum: Checksum = checksum
         .read()
         .ok_or_else(|| Error::unset_arg(CHECKSUM_ARG))?
         .try_into()?;
-    let report = cache.analyze(&checksum)?;
-    Ok(report.into())
+    let report: AnalysisReport = cache.analyze(&checksum)?.into();
+    Ok(to_vec(&report)?)
+}
+
+/// Analyzes every checksum in `checksums` (a serialized `Vec<Checksum>`) in
+/// one FFI round trip. Like `pin_batch`, this is a loop over the existing
+/// single-item `Cache::analyze` and does not change how many times the
+/// cache's internal lock is taken — only the number of FFI crossings drops
+/// from one per checksum to one per call.
+///
+/// Returns a serialized `Vec<Result<AnalysisReport, String>>`, one entry per
+/// input checksum in order. A failure analyzing one checksum does not stop
+/// the rest from being attempted, mirroring `pin_batch`.
+#[no_mangle]
+pub extern "C" fn analyze_code_batch(
+    cache: *mut cache_t,
+    checksums: ByteSliceView,
+    error_msg: Option<&mut UnmanagedVector>,
+) -> UnmanagedVector {
+    let r = match to_cache(cache) {
+        Some(c) => catch_unwind(AssertUnwindSafe(move || do_analyze_code_batch(c, checksums)))
+            .unwrap_or_else(|_| Err(Error::panic())),
+        None => Err(Error::unset_arg(CACHE_ARG)),
+    };
+    let data = handle_c_error_binary(r, error_msg);
+    UnmanagedVector::new(Some(data))
+}
+
+fn do_analyze_code_batch(
+    cache: &mut Cache<GoApi, GoStorage, GoQuerier>,
+    checksums: ByteSliceView,
+) -> Result<Vec<u8>, Error> {
+    let checksums: Vec<Checksum> = from_slice(
+        &checksums
+            .read()
+            .ok_or_else(|| Error::unset_arg(CHECKSUM_ARG))?,
+    )?;
+    let mut reports: Vec<Result<AnalysisReport, String>> = Vec::with_capacity(checksums.len());
+    for checksum in &checksums {
+        reports.push(match cache.analyze(checksum) {
+            Ok(report) => Ok(report.into()),
+            Err(e) => Err(e.to_string()),
+        });
+    }
+    Ok(to_vec(&reports)?)
 }
 
 /// frees a cache reference
@@ -517,10 +622,13 @@ mod tests {
             Some(&mut error_msg),
         );
         let _ = error_msg.consume();
+        let hackatom_report: AnalysisReport =
+            from_slice(&hackatom_report.consume().unwrap()).unwrap();
         assert_eq!(
             hackatom_report,
             AnalysisReport {
-                has_ibc_entry_points: false
+                has_ibc_entry_points: false,
+                ..Default::default()
             }
         );
 
@@ -531,13 +639,123 @@ mod tests {
             Some(&mut error_msg),
         );
         let _ = error_msg.consume();
+        let ibc_reflect_report: AnalysisReport =
+            from_slice(&ibc_reflect_report.consume().unwrap()).unwrap();
         assert_eq!(
             ibc_reflect_report,
             AnalysisReport {
-                has_ibc_entry_points: true
+                has_ibc_entry_points: true,
+                ..Default::default()
             }
         );
 
         release_cache(cache_ptr);
     }
+
+    #[test]
+    fn pin_batch_works() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let features: &[u8] = b"staking";
+
+        let mut error_msg = UnmanagedVector::default();
+        let cache_ptr = init_cache(
+            ByteSliceView::new(dir.as_bytes()),
+            ByteSliceView::new(features),
+            512,
+            32,
+            Some(&mut error_msg),
+        );
+        assert_eq!(error_msg.is_none(), true);
+        let _ = error_msg.consume();
+
+        let mut error_msg = UnmanagedVector::default();
+        let checksum = save_wasm(
+            cache_ptr,
+            ByteSliceView::new(HACKATOM),
+            Some(&mut error_msg),
+        );
+        assert_eq!(error_msg.is_none(), true);
+        let _ = error_msg.consume();
+        let checksum_bytes = checksum.consume().unwrap_or_default();
+        let checksum: Checksum = checksum_bytes.as_slice().try_into().unwrap();
+
+        let unknown_checksum: Checksum = [0u8; 32].as_slice().try_into().unwrap();
+
+        let checksums = to_vec(&vec![checksum, unknown_checksum]).unwrap();
+
+        let mut error_msg = UnmanagedVector::default();
+        let results = pin_batch(
+            cache_ptr,
+            ByteSliceView::new(&checksums),
+            Some(&mut error_msg),
+        );
+        assert_eq!(error_msg.is_none(), true);
+        let _ = error_msg.consume();
+        let results: Vec<Option<String>> = from_slice(&results.consume().unwrap()).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_none(), "pinning a known checksum should succeed");
+        assert!(
+            results[1].is_some(),
+            "pinning an unknown checksum should fail without aborting the rest of the batch"
+        );
+
+        release_cache(cache_ptr);
+    }
+
+    #[test]
+    fn analyze_code_batch_works() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let features: &[u8] = b"stargate";
+
+        let mut error_msg = UnmanagedVector::default();
+        let cache_ptr = init_cache(
+            ByteSliceView::new(dir.as_bytes()),
+            ByteSliceView::new(features),
+            512,
+            32,
+            Some(&mut error_msg),
+        );
+        assert_eq!(error_msg.is_none(), true);
+        let _ = error_msg.consume();
+
+        let mut error_msg = UnmanagedVector::default();
+        let checksum_hackatom = save_wasm(
+            cache_ptr,
+            ByteSliceView::new(HACKATOM),
+            Some(&mut error_msg),
+        );
+        assert_eq!(error_msg.is_none(), true);
+        let _ = error_msg.consume();
+        let checksum_hackatom_bytes = checksum_hackatom.consume().unwrap_or_default();
+        let checksum_hackatom: Checksum = checksum_hackatom_bytes.as_slice().try_into().unwrap();
+
+        let unknown_checksum: Checksum = [0u8; 32].as_slice().try_into().unwrap();
+
+        let checksums = to_vec(&vec![checksum_hackatom, unknown_checksum]).unwrap();
+
+        let mut error_msg = UnmanagedVector::default();
+        let reports = analyze_code_batch(
+            cache_ptr,
+            ByteSliceView::new(&checksums),
+            Some(&mut error_msg),
+        );
+        assert_eq!(error_msg.is_none(), true);
+        let _ = error_msg.consume();
+        let reports: Vec<Result<AnalysisReport, String>> =
+            from_slice(&reports.consume().unwrap()).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports[0],
+            Ok(AnalysisReport {
+                has_ibc_entry_points: false,
+                ..Default::default()
+            })
+        );
+        assert!(
+            reports[1].is_err(),
+            "analyzing an unknown checksum should fail without aborting the rest of the batch"
+        );
+
+        release_cache(cache_ptr);
+    }
 }
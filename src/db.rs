@@ -0,0 +1,183 @@
+use crate::error::GoResult;
+use crate::memory::{U8SliceView, UnmanagedVector};
+
+// this represents something passed in from the caller side of FFI
+#[repr(C)]
+pub struct db_t {
+    _private: [u8; 0],
+}
+
+// These functions should return GoResult but because we don't trust them here, we treat the return value as i32
+// and then check it when converting to GoResult manually
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Db_vtable {
+    pub read_db: extern "C" fn(
+        *mut db_t,
+        U8SliceView,          // key
+        *mut UnmanagedVector, // value output
+        *mut UnmanagedVector, // error message output
+        *mut u64,
+    ) -> i32,
+    pub write_db: extern "C" fn(
+        *mut db_t,
+        U8SliceView,          // key
+        U8SliceView,          // value
+        *mut UnmanagedVector, // error message output
+        *mut u64,
+    ) -> i32,
+    pub remove_db: extern "C" fn(
+        *mut db_t,
+        U8SliceView,          // key
+        *mut UnmanagedVector, // error message output
+        *mut u64,
+    ) -> i32,
+    // Takes a snapshot of the current storage state and hands back an opaque
+    // id a later `revert_to_checkpoint`/`discard_checkpoint` call consumes.
+    // Lets a caller stack several of these (one per nested `contract_call`)
+    // and unwind them one frame at a time, the same way nested SQL
+    // savepoints do.
+    pub checkpoint: extern "C" fn(
+        *mut db_t,
+        *mut u64,             // checkpoint id output
+        *mut UnmanagedVector, // error message output
+    ) -> i32,
+    // Discards every write made since `checkpoint_id` was taken.
+    pub revert_to_checkpoint: extern "C" fn(
+        *mut db_t,
+        u64, // checkpoint id
+        *mut UnmanagedVector, // error message output
+    ) -> i32,
+    // Drops `checkpoint_id` without reverting, keeping every write made
+    // since it was taken. Must still be called on the success path so the
+    // Go side can release whatever bookkeeping it used to track the
+    // checkpoint.
+    pub discard_checkpoint: extern "C" fn(
+        *mut db_t,
+        u64, // checkpoint id
+        *mut UnmanagedVector, // error message output
+    ) -> i32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Db {
+    pub state: *mut db_t,
+    pub vtable: Db_vtable,
+}
+
+// We must declare that these are safe to Send, to use in wasm.
+// The known go caller passes in immutable function pointers, but this is indeed
+// unsafe for possible other callers.
+unsafe impl Send for Db {}
+
+/// An opaque handle to a storage savepoint taken by [`Db::checkpoint`]. Only
+/// meaningful as an argument to the same `Db`'s `revert_to_checkpoint`/
+/// `discard_checkpoint` that produced it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint(u64);
+
+impl Db {
+    /// Takes a storage savepoint. Panics if the Go side reports an error,
+    /// matching how infallible-in-practice FFI calls are already treated
+    /// elsewhere in this crate (e.g. `write_value_to_env`) -- a checkpoint
+    /// failing is not a condition callers are expected to recover from.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let mut checkpoint_id = 0_u64;
+        let mut error_msg = UnmanagedVector::default();
+        let go_result: GoResult =
+            (self.vtable.checkpoint)(self.state, &mut checkpoint_id as *mut u64, &mut error_msg as *mut UnmanagedVector)
+                .into();
+        unsafe {
+            go_result
+                .into_ffi_result(error_msg, || "Failed to take storage checkpoint".to_string())
+                .unwrap();
+        }
+        Checkpoint(checkpoint_id)
+    }
+
+    pub fn revert_to_checkpoint(&self, checkpoint: Checkpoint) {
+        let mut error_msg = UnmanagedVector::default();
+        let go_result: GoResult =
+            (self.vtable.revert_to_checkpoint)(self.state, checkpoint.0, &mut error_msg as *mut UnmanagedVector)
+                .into();
+        unsafe {
+            go_result
+                .into_ffi_result(error_msg, || {
+                    "Failed to revert to storage checkpoint".to_string()
+                })
+                .unwrap();
+        }
+    }
+
+    pub fn discard_checkpoint(&self, checkpoint: Checkpoint) {
+        let mut error_msg = UnmanagedVector::default();
+        let go_result: GoResult =
+            (self.vtable.discard_checkpoint)(self.state, checkpoint.0, &mut error_msg as *mut UnmanagedVector)
+                .into();
+        unsafe {
+            go_result
+                .into_ffi_result(error_msg, || {
+                    "Failed to discard storage checkpoint".to_string()
+                })
+                .unwrap();
+        }
+    }
+
+    pub(crate) fn read(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let mut output = UnmanagedVector::default();
+        let mut error_msg = UnmanagedVector::default();
+        let mut used_gas = 0_u64;
+        let go_result: GoResult = (self.vtable.read_db)(
+            self.state,
+            U8SliceView::new(Some(key)),
+            &mut output as *mut UnmanagedVector,
+            &mut error_msg as *mut UnmanagedVector,
+            &mut used_gas as *mut u64,
+        )
+        .into();
+        unsafe {
+            go_result
+                .into_ffi_result(error_msg, || "Failed to read from storage".to_string())
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(output.consume())
+    }
+
+    pub(crate) fn write(&self, key: &[u8], value: &[u8]) -> Result<(), String> {
+        let mut error_msg = UnmanagedVector::default();
+        let mut used_gas = 0_u64;
+        let go_result: GoResult = (self.vtable.write_db)(
+            self.state,
+            U8SliceView::new(Some(key)),
+            U8SliceView::new(Some(value)),
+            &mut error_msg as *mut UnmanagedVector,
+            &mut used_gas as *mut u64,
+        )
+        .into();
+        unsafe {
+            go_result
+                .into_ffi_result(error_msg, || "Failed to write to storage".to_string())
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove(&self, key: &[u8]) -> Result<(), String> {
+        let mut error_msg = UnmanagedVector::default();
+        let mut used_gas = 0_u64;
+        let go_result: GoResult = (self.vtable.remove_db)(
+            self.state,
+            U8SliceView::new(Some(key)),
+            &mut error_msg as *mut UnmanagedVector,
+            &mut used_gas as *mut u64,
+        )
+        .into();
+        unsafe {
+            go_result
+                .into_ffi_result(error_msg, || "Failed to remove from storage".to_string())
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use cosmwasm_vm::{BackendError, BackendResult, GasInfo, Order, Record, Storage};
+
+use crate::db::{Checkpoint, Db};
+
+/// Wraps the raw FFI [`Db`] handle with the `cosmwasm_vm::Storage` trait so it
+/// can be plugged into a `Backend<GoApi, GoStorage, GoQuerier>` like any other
+/// storage implementation.
+pub struct GoStorage {
+    db: Db,
+}
+
+impl GoStorage {
+    pub fn new(db: Db) -> Self {
+        GoStorage { db }
+    }
+
+    /// Forwards to the underlying `Db`'s checkpoint API, so callers that only
+    /// hold a `GoStorage` (rather than the `Db` handle used to build it) can
+    /// still take part in the same nested-call savepoint scheme `api.rs`
+    /// uses directly on `Db`.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.db.checkpoint()
+    }
+
+    pub fn revert_to_checkpoint(&self, checkpoint: Checkpoint) {
+        self.db.revert_to_checkpoint(checkpoint)
+    }
+
+    pub fn discard_checkpoint(&self, checkpoint: Checkpoint) {
+        self.db.discard_checkpoint(checkpoint)
+    }
+}
+
+impl Storage for GoStorage {
+    fn get(&self, key: &[u8]) -> BackendResult<Option<Vec<u8>>> {
+        let result = self
+            .db
+            .read(key)
+            .map_err(BackendError::unknown);
+        (result, GasInfo::with_cost(0))
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) -> BackendResult<()> {
+        let result = self.db.write(key, value).map_err(BackendError::unknown);
+        (result, GasInfo::with_cost(0))
+    }
+
+    fn remove(&mut self, key: &[u8]) -> BackendResult<()> {
+        let result = self.db.remove(key).map_err(BackendError::unknown);
+        (result, GasInfo::with_cost(0))
+    }
+
+    fn scan(
+        &mut self,
+        _start: Option<&[u8]>,
+        _end: Option<&[u8]>,
+        _order: Order,
+    ) -> BackendResult<u32> {
+        // Iteration is not supported by `GoStorage`: `scan` and `next` always
+        // return an error.
+        (
+            Err(BackendError::unknown("scan is not implemented for GoStorage")),
+            GasInfo::with_cost(0),
+        )
+    }
+
+    fn next(&mut self, _iterator_id: u32) -> BackendResult<Option<Record>> {
+        (
+            Err(BackendError::unknown("next is not implemented for GoStorage")),
+            GasInfo::with_cost(0),
+        )
+    }
+}